@@ -2,25 +2,35 @@ use std::collections::HashMap;
 use std::fs::{File, read_dir};
 use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, bail};
 use once_cell::sync;
 use once_cell::unsync::Lazy;
+use quick_xml::Writer;
+use quick_xml::events::{BytesDecl, BytesText, Event};
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use rayon::{ThreadPool, ThreadPoolBuilder};
-use serde::Deserialize;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
 use serde_with::{DefaultOnNull, NoneAsEmptyString, serde_as};
 use zip::ZipWriter;
 use zip::write::FileOptions;
 
-use crate::CONFIG;
+use crate::{CONFIG, Compression, ImageQuality};
+use crate::closing::{self, ShutdownGuard};
 use crate::groups::{get_all_groups, groups_in_chapter};
-use crate::manga::{MangaInfo, get_or_create_dir};
-use crate::util::{FindResult, convert_filename, convert_uuid, find_existing, http_get, json_get};
+use crate::manga::{MangaInfo, fetch_manga_info, get_or_create_dir};
+use crate::util::{
+    FindResult, archive_extension, convert_filename, convert_uuid, english_or_first, find_existing,
+    http_get, json_get, post_fire_and_forget,
+};
+
+const AT_HOME_REPORT_URL: &str = "https://api.mangadex.network/report";
 
 static DOWNLOADERS: sync::Lazy<ThreadPool> = sync::Lazy::new(|| {
     ThreadPoolBuilder::new()
-        .num_threads(CONFIG.parallel_downloads.get() as usize)
+        .num_threads(CONFIG.read().unwrap().parallel_downloads.get() as usize)
         .thread_name(|i| format!("downloader-{i}"))
         .build()
         .unwrap()
@@ -39,19 +49,94 @@ pub fn sync_single_chapter(chapter_id: String) -> Result<()> {
         .next()
         .context("Chapter has no associated manga")?;
 
-    let info: MangaInfo = json_get(format!("https://api.mangadex.org/manga/{manga_id}"))?;
+    let info: MangaInfo = fetch_manga_info(manga_id)?;
+    let manga_title =
+        english_or_first(&info.data.attributes.title).context("No title present")?;
     let manga_dir = get_or_create_dir(&info)?;
 
     let chapters = [chapter.data];
     let groups = get_all_groups(&chapters)?;
 
-    sync_chapters(chapters.into_iter(), &manga_dir, &groups, false)
+    sync_chapters(chapters.into_iter(), &manga_title, &manga_dir, &groups, false)
 }
 
-fn download_chapter(chapter: &Chapter, archive_path: PathBuf) -> Result<()> {
+// Builds a ComicInfo.xml payload understood by Komga, Tachiyomi/Mihon, and similar readers.
+fn comic_info_xml(manga_title: &str, chapter: &Chapter, groups: &str, page_count: usize) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut writer = Writer::new_with_indent(&mut buf, b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+
+    writer
+        .create_element("ComicInfo")
+        .write_inner_content(|writer| {
+            writer.create_element("Series").write_text_content(BytesText::new(manga_title))?;
+
+            if let Some(title) = &chapter.attributes.title {
+                writer.create_element("Title").write_text_content(BytesText::new(title))?;
+            }
+            if let Some(chapter_number) = &chapter.attributes.chapter {
+                writer.create_element("Number").write_text_content(BytesText::new(chapter_number))?;
+            }
+            if let Some(volume) = &chapter.attributes.volume {
+                writer.create_element("Volume").write_text_content(BytesText::new(volume))?;
+            }
+            if !groups.is_empty() {
+                writer.create_element("Translator").write_text_content(BytesText::new(groups))?;
+                writer.create_element("Teams").write_text_content(BytesText::new(groups))?;
+            }
+
+            writer
+                .create_element("LanguageISO")
+                .write_text_content(BytesText::new(&CONFIG.read().unwrap().language))?;
+            writer
+                .create_element("PageCount")
+                .write_text_content(BytesText::new(&page_count.to_string()))?;
+            writer
+                .create_element("Web")
+                .write_text_content(BytesText::new(&format!("https://mangadex.org/chapter/{}", chapter.id)))?;
+
+            Ok(())
+        })?;
+
+    Ok(buf)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AtHomeReport {
+    url: String,
+    success: bool,
+    cached: bool,
+    bytes: u64,
+    duration: u64,
+}
+
+// Mangadex@Home servers rely on these reports to gauge their own health, so we submit one for
+// every page fetched from a server other than mangadex.org itself.
+fn report_at_home(url: &str, success: bool, cached: bool, bytes: u64, duration: Duration) {
+    post_fire_and_forget(
+        AT_HOME_REPORT_URL,
+        &AtHomeReport { url: url.to_string(), success, cached, bytes, duration: duration.as_millis() as u64 },
+    );
+}
+
+fn download_chapter(
+    chapter: &Chapter,
+    manga_title: &str,
+    groups: &str,
+    archive_path: PathBuf,
+) -> Result<()> {
+    // Bounds how many chapter downloads/archive writes run at once, independently of the rayon
+    // pool size.
+    let _permit = closing::acquire()?;
+    // Held for the rest of the download so a shutdown signal can drain us before exiting rather
+    // than interrupting a partially-written archive.
+    let _guard = ShutdownGuard::acquire();
+
     let mut builder = tempfile::Builder::new();
     builder.prefix("manga-syncer");
     let tmp_dir = CONFIG
+        .read()
+        .unwrap()
         .temp_directory
         .as_ref()
         .map_or_else(|| builder.tempdir(), |d| builder.tempdir_in(d))?;
@@ -59,17 +144,24 @@ fn download_chapter(chapter: &Chapter, archive_path: PathBuf) -> Result<()> {
     let at_home: AtHomeResponse =
         json_get(format!("https://api.mangadex.org/at-home/server/{}", chapter.id))?;
 
-    if chapter.attributes.external_url.is_some() && at_home.chapter.data.is_empty() {
+    let (page_paths, path_segment) = match CONFIG.read().unwrap().image_quality {
+        ImageQuality::Full => (&at_home.chapter.data, "data"),
+        ImageQuality::DataSaver => (&at_home.chapter.data_saver, "data-saver"),
+    };
+
+    if chapter.attributes.external_url.is_some() && page_paths.is_empty() {
         debug!("Skipping chapter {} with external url and no pages", chapter.id);
         return Ok(());
-    } else if at_home.chapter.data.is_empty() {
+    } else if page_paths.is_empty() {
         bail!("Got chapter with no pages: {chapter:?}\n{at_home:?}");
     }
 
+    // Health reports are only meaningful for the @Home network, not MangaDex's own servers.
+    let report_to_network = Url::parse(&at_home.base_url)
+        .map(|u| !u.host_str().is_some_and(|h| h == "mangadex.org" || h.ends_with(".mangadex.org")))
+        .unwrap_or(true);
 
-    let mut paths = at_home
-        .chapter
-        .data
+    let mut paths = page_paths
         .iter()
         .enumerate()
         .par_bridge()
@@ -82,15 +174,33 @@ fn download_chapter(chapter: &Chapter, archive_path: PathBuf) -> Result<()> {
             let filename = format!("{:03}.{ext}", (i + 1));
             let filepath = tmp_dir.path().join(filename);
 
-            let url = at_home.base_url.clone() + "/data/" + &at_home.chapter.hash + "/" + p;
+            let url = at_home.base_url.clone() + "/" + path_segment + "/" + &at_home.chapter.hash + "/" + p;
 
             trace!("Downloading {url:?} to {filepath:?}");
 
             let download = || {
                 let mut file = BufWriter::new(File::create(&filepath)?);
-                let mut contents = http_get(&url)?;
 
-                let n = io::copy(&mut contents, &mut file)?;
+                let start = Instant::now();
+                let result: Result<(u64, bool)> = (|| {
+                    let mut contents = http_get(&url)?;
+                    let cached = contents
+                        .headers()
+                        .get("x-cache")
+                        .and_then(|v| v.to_str().ok())
+                        .is_some_and(|v| v.starts_with("HIT"));
+                    Ok((io::copy(&mut contents, &mut file)?, cached))
+                })();
+
+                if report_to_network {
+                    let (success, cached, bytes) = match &result {
+                        Ok((n, cached)) => (true, *cached, *n),
+                        Err(_) => (false, false, 0),
+                    };
+                    report_at_home(&url, success, cached, bytes, start.elapsed());
+                }
+
+                let (n, _cached) = result?;
                 if n == 0 {
                     bail!("Wrote empty file to {filepath:?}");
                 }
@@ -116,8 +226,19 @@ fn download_chapter(chapter: &Chapter, archive_path: PathBuf) -> Result<()> {
     let temp_zip = tmp_dir.path().join("output.zip");
     let outfile = BufWriter::new(File::create(&temp_zip)?);
 
+    let compression_method = match CONFIG.read().unwrap().compression {
+        Compression::Stored => zip::CompressionMethod::Stored,
+        Compression::Deflate => zip::CompressionMethod::Deflated,
+        Compression::Zstd => zip::CompressionMethod::Zstd,
+    };
+
     let mut zip = ZipWriter::new(outfile);
-    let options = FileOptions::<()>::default().unix_permissions(0o755);
+    let options =
+        FileOptions::<()>::default().compression_method(compression_method).unix_permissions(0o755);
+
+    let comic_info = comic_info_xml(manga_title, chapter, groups, paths.len())?;
+    zip.start_file("ComicInfo.xml", options)?;
+    zip.write_all(&comic_info)?;
 
     let mut buffer = Vec::new();
     for p in paths {
@@ -140,6 +261,7 @@ fn download_chapter(chapter: &Chapter, archive_path: PathBuf) -> Result<()> {
 
 pub fn sync_chapters(
     chapters: impl Iterator<Item = Chapter>,
+    manga_title: &str,
     manga_dir: &Path,
     groups: &HashMap<&str, &str>,
     continue_on_error: bool,
@@ -175,7 +297,7 @@ pub fn sync_chapters(
         } else {
             convert_filename(&format!("{name} [{groups}]"))
         };
-        let filename = filename + " - " + &converted_id + ".zip";
+        let filename = filename + " - " + &converted_id + "." + archive_extension();
 
         let chapter_path = manga_dir.join(filename);
 
@@ -186,7 +308,7 @@ pub fn sync_chapters(
                 continue;
             }
             FindResult::RenameCandidate(path) => {
-                if CONFIG.rename_chapters {
+                if CONFIG.read().unwrap().rename_chapters {
                     info!("Renaming existing chapter from {path:?} to {chapter_path:?}");
                     std::fs::rename(path, &chapter_path)?;
                 } else {
@@ -197,7 +319,7 @@ pub fn sync_chapters(
         }
 
         if let Err(e) = DOWNLOADERS
-            .install(|| download_chapter(&c, chapter_path))
+            .install(|| download_chapter(&c, manga_title, &groups, chapter_path))
             .with_context(|| format!("Failed while downloading chapter {}", c.id))
         {
             if continue_on_error {
@@ -223,6 +345,8 @@ struct AtHomeResponse {
 struct AtHomeChapter {
     pub hash: String,
     pub data: Vec<String>,
+    #[serde(default)]
+    pub data_saver: Vec<String>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
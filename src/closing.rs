@@ -1,30 +1,137 @@
 use std::env::temp_dir;
 use std::io::Write;
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::JoinHandle;
+use std::time::Duration;
 use std::{process, thread};
 
 use anyhow::anyhow;
 use once_cell::sync::Lazy;
 
+use crate::CONFIG;
+
 static CLOSED: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(false)));
 
+// Bumped every time SIGHUP is received. Unlike CLOSED this never triggers a shutdown: it's a
+// cheap way for main's per-manga loop to notice a config reload request.
+static RELOAD_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// How long the signal thread waits for in-flight work to drain before it stops waiting.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+static LIVE_GUARDS: Lazy<(Mutex<usize>, Condvar)> = Lazy::new(|| (Mutex::new(0), Condvar::new()));
+
+// Who sent the signal that triggered the current shutdown, if any, for the crash report.
+static SIGNAL_ORIGIN: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// RAII token held by workers doing something that shouldn't be interrupted mid-write, such as
+/// an archive download. Acquire it before starting the work and let it drop when done; `drain`
+/// waits for the count of live guards to reach zero before the process exits.
+pub struct ShutdownGuard {
+    _private: (),
+}
+
+impl ShutdownGuard {
+    pub fn acquire() -> Self {
+        *LIVE_GUARDS.0.lock().unwrap() += 1;
+        Self { _private: () }
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        let mut count = LIVE_GUARDS.0.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            LIVE_GUARDS.1.notify_all();
+        }
+    }
+}
+
+/// Blocks until every live `ShutdownGuard` has been dropped or `timeout` elapses. Returns `true`
+/// if draining completed, `false` if it timed out with tasks still running.
+pub fn drain(timeout: Duration) -> bool {
+    let count = LIVE_GUARDS.0.lock().unwrap();
+    let (count, result) = LIVE_GUARDS.1.wait_timeout_while(count, timeout, |c| *c > 0).unwrap();
+
+    if result.timed_out() {
+        warn!("Timed out after {timeout:?} waiting for {} in-flight task(s) to finish", *count);
+        false
+    } else {
+        true
+    }
+}
+
 pub fn err_if_closed() -> anyhow::Result<()> {
     if CLOSED.load(Ordering::Relaxed) { Err(anyhow!("Closed")) } else { Ok(()) }
 }
 
+static PERMITS: Lazy<(Mutex<usize>, Condvar)> = Lazy::new(|| {
+    (Mutex::new(CONFIG.read().unwrap().max_concurrent_operations.get() as usize), Condvar::new())
+});
+
+/// A slot returned by `acquire`. Dropping it frees the slot for the next waiter.
+pub struct Permit {
+    _private: (),
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *PERMITS.0.lock().unwrap() += 1;
+        PERMITS.1.notify_one();
+    }
+}
+
+/// Blocks until a concurrency slot is free, bounding how many chapter downloads and archive
+/// operations can run at once. Returns `Err` immediately once the application starts shutting
+/// down; `close()` wakes every blocked waiter so it can return `Err` too.
+pub fn acquire() -> anyhow::Result<Permit> {
+    let mut count = PERMITS.0.lock().unwrap();
+    loop {
+        err_if_closed()?;
+
+        if *count > 0 {
+            *count -= 1;
+            return Ok(Permit { _private: () });
+        }
+
+        // close() notifies this condvar so a waiter never blocks past shutdown.
+        count = PERMITS.1.wait(count).unwrap();
+    }
+}
+
+/// Returns whether a reload has been requested since `last_seen`, updating it in place.
+///
+/// Callers should pass `&mut 0` the first time and keep reusing the same variable afterwards.
+pub fn reload_pending(last_seen: &mut u64) -> bool {
+    let current = RELOAD_GENERATION.load(Ordering::SeqCst);
+    if current == *last_seen {
+        return false;
+    }
+    *last_seen = current;
+    true
+}
+
 pub fn close() -> bool {
-    !CLOSED.swap(true, Ordering::Relaxed)
+    let first = !CLOSED.swap(true, Ordering::Relaxed);
+    // Take the permit lock before notifying so a waiter that already checked err_if_closed()/
+    // count and is about to call wait() can't miss this wakeup: it either observes CLOSED before
+    // taking the lock, or it's already blocked in wait() and this notify reaches it.
+    let _guard = PERMITS.0.lock().unwrap();
+    PERMITS.1.notify_all();
+    first
 }
 
 // Logs the error and closes the application.
-// Saves the first fatal error to a crash log file in the system default temp directory.
-pub fn fatal(msg: impl AsRef<str>) {
+// Saves the first fatal error to a crash log file in the system default temp directory, along
+// with a backtrace and enough metadata to make sense of an unattended run after the fact.
+pub fn fatal(source: impl AsRef<str>, msg: impl AsRef<str>) {
+    let source = source.as_ref();
     let msg = msg.as_ref();
 
-    error!("{msg}");
+    error!("[{source}] {msg}");
 
     if close() {
         let path = temp_dir().join(format!("manga-syncer_crash_{}", process::id()));
@@ -33,17 +140,58 @@ pub fn fatal(msg: impl AsRef<str>) {
             return;
         };
 
-        drop(file.write_all(msg.as_bytes()));
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        let signal_origin = SIGNAL_ORIGIN.lock().unwrap().clone();
+        let signal_line =
+            signal_origin.map(|o| format!("signal: {o}\n")).unwrap_or_default();
+
+        let report = format!(
+            "manga-syncer {} crash report\n\
+             time: {}\n\
+             pid: {}\n\
+             source: {source}\n\
+             {signal_line}\
+             \n\
+             {msg}\n\
+             \n\
+             backtrace:\n\
+             {backtrace}\n",
+            env!("CARGO_PKG_VERSION"),
+            chrono::Local::now().to_rfc3339(),
+            process::id(),
+        );
+
+        drop(file.write_all(report.as_bytes()));
     }
 }
 
+// Installs a global panic hook so a panic on any thread is recorded as the fatal crash cause,
+// instead of silently killing that thread while the rest of the process limps along.
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info.location().map_or_else(|| "unknown location".to_string(), ToString::to_string);
+        let payload = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+
+        fatal("panic", format!("Thread panicked at {location}: {payload}"));
+        default_hook(info);
+    }));
+}
+
 pub fn init() -> std::io::Result<JoinHandle<()>> {
+    install_panic_hook();
+
     #[cfg(target_family = "unix")]
     let f = || {
         use std::os::raw::c_int;
 
-        use signal_hook::consts::TERM_SIGNALS;
-        use signal_hook::iterator::exfiltrator::SignalOnly;
+        use signal_hook::consts::{SIGHUP, TERM_SIGNALS};
+        use signal_hook::iterator::exfiltrator::WithOrigin;
         use signal_hook::iterator::SignalsInfo;
 
         if let Err(e) = catch_unwind(AssertUnwindSafe(|| {
@@ -52,24 +200,42 @@ pub fn init() -> std::io::Result<JoinHandle<()>> {
                 signal_hook::flag::register_conditional_shutdown(*sig, 1, CLOSED.clone())
                     .expect("Error registering signal handlers.");
             }
+            // SIGHUP is intentionally left out of the conditional-shutdown flags above: it means
+            // "reload", not "quit".
 
             let mut sigs: Vec<c_int> = Vec::new();
             sigs.extend(TERM_SIGNALS);
-            let mut it = match SignalsInfo::<SignalOnly>::new(sigs) {
+            sigs.push(SIGHUP);
+            let mut it = match SignalsInfo::<WithOrigin>::new(sigs) {
                 Ok(i) => i,
                 Err(e) => {
-                    fatal(format!("Error registering signal handlers: {e:?}"));
+                    fatal("signal registration", format!("Error registering signal handlers: {e:?}"));
                     return;
                 }
             };
 
-            if let Some(s) = it.into_iter().next() {
-                info!("Received signal {s}, shutting down");
+            for info in &mut it {
+                let s = info.signal;
+                let origin = info
+                    .process
+                    .map(|p| format!("pid {} (uid {})", p.pid, p.uid))
+                    .unwrap_or_else(|| "an unknown origin".to_string());
+
+                if s == SIGHUP {
+                    let generation = RELOAD_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+                    info!("Received SIGHUP from {origin}, reloading (generation {generation})");
+                    continue;
+                }
+
+                info!("Received signal {s} from {origin}, shutting down");
+                *SIGNAL_ORIGIN.lock().unwrap() = Some(format!("signal {s} from {origin}"));
                 close();
                 it.handle().close();
+                drain(DRAIN_TIMEOUT);
+                break;
             }
         })) {
-            fatal(format!("Signal thread panicked unexpectedly: {e:?}"));
+            fatal("signal thread", format!("Signal thread panicked unexpectedly: {e:?}"));
         };
     };
 
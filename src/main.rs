@@ -4,6 +4,7 @@ extern crate log;
 use std::collections::HashSet;
 use std::num::NonZeroU8;
 use std::path::PathBuf;
+use std::sync::RwLock;
 
 use anyhow::{Context, Result};
 use clap::Parser;
@@ -47,6 +48,9 @@ enum Command {
         #[arg(allow_hyphen_values = true, required=true, num_args=1..)]
         manga_ids: Vec<String>,
     },
+    /// Search MangaDex for a manga by title, then optionally sync it
+    #[command(visible_alias = "s")]
+    Search { query: String },
 }
 
 #[serde_as]
@@ -62,14 +66,76 @@ struct Config {
     blocked_groups: HashSet<String>,
     parallel_downloads: NonZeroU8,
     ignored_chapters: HashSet<String>,
+    // Cap on the exponential backoff between retries of a 5xx or connection failure, in seconds.
+    #[serde(default = "default_max_backoff_secs")]
+    max_backoff_secs: NonZeroU8,
+    // Use the .cbz extension instead of .zip, since the archives now contain ComicInfo.xml.
+    #[serde(default)]
+    cbz: bool,
+    #[serde(default)]
+    image_quality: ImageQuality,
+    #[serde(default)]
+    ascii_filenames: bool,
+    #[serde(default)]
+    compression: Compression,
+    #[serde(default)]
+    download_cover: bool,
+    #[serde(default)]
+    cover_thumbnail: bool,
+    #[serde(default = "default_max_concurrent_operations")]
+    max_concurrent_operations: NonZeroU8,
 }
 
-static CONFIG: Lazy<Config> = Lazy::new(|| {
+fn default_max_concurrent_operations() -> NonZeroU8 {
+    NonZeroU8::new(4).unwrap()
+}
+
+fn default_max_backoff_secs() -> NonZeroU8 {
+    NonZeroU8::new(30).unwrap()
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ImageQuality {
+    #[default]
+    Full,
+    DataSaver,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Compression {
+    #[default]
+    Stored,
+    Deflate,
+    Zstd,
+}
+
+static CONFIG: Lazy<RwLock<Config>> = Lazy::new(|| RwLock::new(load_config()));
+
+fn load_config() -> Config {
     awconf::load_config::<Config>("manga-syncer", None::<&str>, None::<&str>)
         .unwrap()
         .0
-});
+}
+
+// Re-reads the config file if a SIGHUP reload has been requested since `last_seen`. Thread pool
+// and permit semaphore sizes (`parallel_downloads`, `max_concurrent_operations`) are fixed the
+// first time each is used and aren't affected afterwards, since resizing either isn't supported; every other
+// setting takes effect starting with the next manga synced.
+fn reload_config_if_pending(last_seen: &mut u64) {
+    if !closing::reload_pending(last_seen) {
+        return;
+    }
 
+    match awconf::load_config::<Config>("manga-syncer", None::<&str>, None::<&str>) {
+        Ok((config, _)) => {
+            *CONFIG.write().unwrap() = config;
+            info!("Reloaded configuration");
+        }
+        Err(e) => error!("Failed to reload configuration, keeping the previous one: {e:?}"),
+    }
+}
 
 fn main() -> Result<()> {
     env_logger::init();
@@ -83,13 +149,22 @@ fn main() -> Result<()> {
             cmd: Some(Command::Chapter { chapter_id }),
             ..
         } => sync_single_chapter(chapter_id),
+        Opt {
+            cmd: Some(Command::Search { query }), ..
+        } => manga::search(&query),
         Opt {
             cmd: Some(Command::Manga { manga_ids }), ..
         }
-        | Opt { manga_ids, .. } => manga_ids
-            .into_iter()
-            .map(|mid| manga::sync_manga(&mid).with_context(|| format!("Failed during {mid}")))
-            .collect::<Result<Vec<_>>>()
-            .map(|_| ()),
+        | Opt { manga_ids, .. } => {
+            let mut reload_generation = 0;
+            manga_ids
+                .into_iter()
+                .map(|mid| {
+                    reload_config_if_pending(&mut reload_generation);
+                    manga::sync_manga(&mid).with_context(|| format!("Failed during {mid}"))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(|_| ())
+        }
     }
 }
@@ -1,5 +1,6 @@
-use std::fs::read_dir;
-use std::path::PathBuf;
+use std::fs::{read_dir, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
 use once_cell::unsync::Lazy;
@@ -9,38 +10,75 @@ use serde::Deserialize;
 use crate::chapter::{sync_chapters, Chapter};
 use crate::groups::get_all_groups;
 use crate::util::{
-    convert_filename, convert_uuid, english_or_first, find_existing, json_get, FindResult,
-    LocalizedString, PAGE_SIZE,
+    convert_filename, convert_uuid, english_or_first, find_existing, http_get, json_get,
+    FindResult, LocalizedString, PAGE_SIZE,
 };
 use crate::CONFIG;
 
+pub fn fetch_manga_info(manga_id: &str) -> Result<MangaInfo> {
+    let mut url = Url::parse(&format!("https://api.mangadex.org/manga/{manga_id}"))?;
+    url.query_pairs_mut().append_pair("includes[]", "cover_art");
+    json_get(url)
+}
+
 pub fn sync_manga(manga_id: &str) -> Result<()> {
     debug!("Syncing {manga_id}");
-    let info: MangaInfo = json_get(format!("https://api.mangadex.org/manga/{manga_id}"))?;
+    let info: MangaInfo = fetch_manga_info(manga_id)?;
     let dir = get_or_create_dir(&info)?;
 
     let chapters = get_all_chapters(manga_id)?;
     let groups = get_all_groups(&chapters)?;
-    debug!(
-        "Got {} chapters for \"{}\"",
-        chapters.len(),
-        english_or_first(&info.data.attributes.title).unwrap()
-    );
+    let title = english_or_first(&info.data.attributes.title).context("No title present")?;
+    debug!("Got {} chapters for \"{title}\"", chapters.len());
 
-    sync_chapters(chapters.into_iter(), &dir, &groups)
+    sync_chapters(chapters.into_iter(), &title, &dir, &groups, false)
 }
 
 
+// Looks up manga by title and prompts the user to pick one to sync, since pasting raw UUIDs is
+// not a great discovery flow.
+pub fn search(query: &str) -> Result<()> {
+    let mut url = Url::parse("https://api.mangadex.org/manga")?;
+    url.query_pairs_mut().append_pair("title", query).append_pair("limit", &PAGE_SIZE.to_string());
+
+    let results: MangaSearchList = json_get(url)?;
+
+    if results.data.is_empty() {
+        println!("No results found for {query:?}");
+        return Ok(());
+    }
+
+    for (i, m) in results.data.iter().enumerate() {
+        let title = english_or_first(&m.attributes.title).unwrap_or_else(|| "<untitled>".to_string());
+        println!("{}: {title} — {}", i + 1, m.id);
+    }
+
+    print!("Select a manga to sync (blank to skip): ");
+    io::stdout().flush()?;
+
+    let mut choice = String::new();
+    io::stdin().read_line(&mut choice)?;
+    let choice = choice.trim();
+    if choice.is_empty() {
+        return Ok(());
+    }
+
+    let index: usize = choice.parse().context("Invalid selection")?;
+    let manga = results.data.get(index.wrapping_sub(1)).context("Selection out of range")?;
+    sync_manga(&manga.id)
+}
+
 pub fn get_or_create_dir(info: &MangaInfo) -> Result<PathBuf> {
     let converted_id = convert_uuid(&info.data.id)?;
     let title = english_or_first(&info.data.attributes.title).context("No title present")?;
     let dir_name = format!("{} - {converted_id}", convert_filename(&title));
-    let mut dir_path = CONFIG.output_directory.join(dir_name);
+    let mut dir_path = CONFIG.read().unwrap().output_directory.join(dir_name);
 
     // Could be more efficient with some kind of producer closure returning an iterator
     // Not likely to be worth it. We only really care for chapters in manga, not all manga.
     let existing: Lazy<Result<Vec<_>>> = Lazy::new(|| {
-        let dirs: std::result::Result<Vec<_>, _> = read_dir(&CONFIG.output_directory)?.collect();
+        let dirs: std::result::Result<Vec<_>, _> =
+            read_dir(&CONFIG.read().unwrap().output_directory)?.collect();
         Ok(dirs?)
     });
 
@@ -51,7 +89,7 @@ pub fn get_or_create_dir(info: &MangaInfo) -> Result<PathBuf> {
         }
         FindResult::AlreadyExists => trace!("Directory already exists for \"{title}\""),
         FindResult::RenameCandidate(path) => {
-            if CONFIG.rename_manga {
+            if CONFIG.read().unwrap().rename_manga {
                 info!("Renaming existing directory from {path:?} to {dir_path:?}");
                 std::fs::rename(path, &dir_path)?;
             } else {
@@ -60,9 +98,49 @@ pub fn get_or_create_dir(info: &MangaInfo) -> Result<PathBuf> {
             }
         }
     }
+
+    if CONFIG.read().unwrap().download_cover {
+        // Cover art is a nice-to-have; don't abort the whole sync over a missing or
+        // unreachable cover image.
+        if let Err(e) = download_cover(info, &dir_path) {
+            warn!("Failed to download cover art for manga {}: {e:#}", info.data.id);
+        }
+    }
+
     Ok(dir_path)
 }
 
+// Downloads the manga's cover art into the manga directory, if it isn't already there.
+fn download_cover(info: &MangaInfo, manga_dir: &Path) -> Result<()> {
+    let cover_path = manga_dir.join("cover.jpg");
+    if cover_path.exists() {
+        return Ok(());
+    }
+
+    let Some(cover) = info
+        .data
+        .relationships
+        .iter()
+        .find(|r| r.type_field == "cover_art")
+        .and_then(|r| r.attributes.as_ref())
+    else {
+        debug!("No cover art relationship for manga {}", info.data.id);
+        return Ok(());
+    };
+
+    let filename = if CONFIG.read().unwrap().cover_thumbnail {
+        format!("{}.512.jpg", cover.file_name)
+    } else {
+        cover.file_name.clone()
+    };
+
+    let url = format!("https://uploads.mangadex.org/covers/{}/{filename}", info.data.id);
+    let mut resp = http_get(url)?;
+    let mut file = File::create(&cover_path)?;
+    io::copy(&mut resp, &mut file)?;
+    Ok(())
+}
+
 fn get_all_chapters(manga_id: &str) -> Result<Vec<Chapter>> {
     let mut total = 1;
     let mut offset = 0;
@@ -72,7 +150,7 @@ fn get_all_chapters(manga_id: &str) -> Result<Vec<Chapter>> {
     page_url
         .query_pairs_mut()
         .append_pair("limit", &PAGE_SIZE.to_string())
-        .append_pair("translatedLanguage[]", &CONFIG.language)
+        .append_pair("translatedLanguage[]", &CONFIG.read().unwrap().language)
         .append_pair("order[chapter]", "desc");
 
     let mut chapters = Vec::new();
@@ -100,7 +178,7 @@ fn get_all_chapters(manga_id: &str) -> Result<Vec<Chapter>> {
             }
 
             if c.relationships.iter().any(|r| {
-                r.type_field == "scanlation_group" && CONFIG.blocked_groups.contains(&r.id)
+                r.type_field == "scanlation_group" && CONFIG.read().unwrap().blocked_groups.contains(&r.id)
             }) {
                 debug!("Filtering out chapter {} with blacklisted group", c.id);
                 false
@@ -127,6 +205,8 @@ pub(super) struct MangaInfo {
 pub(super) struct Manga {
     pub id: String,
     pub attributes: MangaAttributes,
+    #[serde(default)]
+    pub relationships: Vec<Relationship>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -135,6 +215,21 @@ pub(super) struct MangaAttributes {
     pub title: LocalizedString,
 }
 
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Relationship {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub attributes: Option<CoverAttributes>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct CoverAttributes {
+    pub file_name: String,
+}
+
 
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -142,3 +237,9 @@ struct ChapterList {
     pub data: Vec<Chapter>,
     pub total: i64,
 }
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MangaSearchList {
+    pub data: Vec<Manga>,
+}
@@ -4,24 +4,33 @@ use std::fmt::Debug;
 use std::fs::DirEntry;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, bail, Result};
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 use once_cell::sync::Lazy;
 use once_cell::unsync;
+use rand::Rng;
 use regex::Regex;
 use reqwest::blocking::{Client, Response};
-use reqwest::IntoUrl;
+use reqwest::{IntoUrl, StatusCode};
+use serde::Serialize;
 use serde::de::DeserializeOwned;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
 use uuid::Uuid;
 
 use crate::closing::err_if_closed;
 use crate::CONFIG;
 
 const DELAY: Duration = Duration::from_millis(1500);
+// Once the remaining rate limit budget drops below this fraction of the limit, start slowing
+// down requests instead of waiting for a 429.
+const PACE_THRESHOLD: f64 = 0.25;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 pub const PAGE_SIZE: usize = 100;
 
@@ -34,24 +43,120 @@ static CLIENT: Lazy<Client> = Lazy::new(|| {
         .unwrap()
 });
 
+// The MangaDex API returns these on every response so we can pace ourselves without waiting to
+// be throttled with a 429.
+static RATE_LIMIT_REMAINING: AtomicU32 = AtomicU32::new(u32::MAX);
+static RATE_LIMIT_LIMIT: AtomicU32 = AtomicU32::new(u32::MAX);
+
+fn record_rate_limit(resp: &Response) {
+    let headers = resp.headers();
+    if let Some(remaining) = headers
+        .get("x-ratelimit-remaining")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+    {
+        RATE_LIMIT_REMAINING.store(remaining, Ordering::Relaxed);
+    }
+    if let Some(limit) =
+        headers.get("x-ratelimit-limit").and_then(|v| v.to_str().ok()).and_then(|v| v.parse().ok())
+    {
+        RATE_LIMIT_LIMIT.store(limit, Ordering::Relaxed);
+    }
+}
+
+// How long to sleep before the next retry of a 429, per the Retry-After or
+// X-RateLimit-Retry-After headers.
+fn rate_limit_delay(resp: &Response) -> Duration {
+    if let Some(secs) =
+        resp.headers().get("retry-after").and_then(|v| v.to_str().ok()).and_then(|v| v.parse().ok())
+    {
+        return Duration::from_secs(secs);
+    }
+
+    if let Some(until) = resp
+        .headers()
+        .get("x-ratelimit-retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        return Duration::from_secs(until.saturating_sub(now));
+    }
+
+    DELAY
+}
+
+fn backoff_with_jitter(backoff: Duration) -> Duration {
+    backoff + Duration::from_millis(rand::thread_rng().gen_range(0..250))
+}
+
 pub fn http_get(url: impl IntoUrl + Clone + Debug) -> Result<Response> {
-    err_if_closed()?;
-    let mut resp = CLIENT.get(url.clone()).send();
-    // Retry up to three times
-    for _ in 0..3 {
-        if resp.is_ok() {
-            break;
+    let max_backoff = Duration::from_secs(CONFIG.read().unwrap().max_backoff_secs.get().into());
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        err_if_closed()?;
+        let resp = CLIENT.get(url.clone()).send();
+
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                if backoff > max_backoff {
+                    return Err(e.into());
+                }
+                debug!("Retrying request {url:?} after failure {e:?}");
+                thread::sleep(backoff_with_jitter(backoff));
+                backoff *= 2;
+                continue;
+            }
+        };
+
+        record_rate_limit(&resp);
+
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => {
+                let delay = rate_limit_delay(&resp);
+                warn!("Rate limited on {url:?}, sleeping {delay:?} before retrying");
+                thread::sleep(delay);
+            }
+            s if s.is_server_error() => {
+                if backoff > max_backoff {
+                    bail!("Request to {url:?} failed after retries with status {s}");
+                }
+                debug!("Retrying request {url:?} after server error {s}");
+                thread::sleep(backoff_with_jitter(backoff));
+                backoff *= 2;
+            }
+            s if s.is_client_error() => {
+                let body = resp.text().unwrap_or_default();
+                bail!("Request to {url:?} failed with status {s}: {body}");
+            }
+            _ => return Ok(resp),
         }
-        debug!("Retrying request {url:?} after failure {:?}", resp.err().unwrap());
-        resp = CLIENT.get(url.clone()).send();
     }
-    err_if_closed()?;
-    Ok(resp?)
+}
+
+// Scales the delay between requests up as the remaining rate limit budget shrinks, rather than
+// always sleeping the same fixed amount.
+fn paced_delay() -> Duration {
+    let remaining = RATE_LIMIT_REMAINING.load(Ordering::Relaxed);
+    let limit = RATE_LIMIT_LIMIT.load(Ordering::Relaxed);
+    if limit == u32::MAX || limit == 0 {
+        return DELAY;
+    }
+
+    let frac = remaining as f64 / limit as f64;
+    if frac >= PACE_THRESHOLD {
+        return DELAY;
+    }
+
+    // Linearly scale up to roughly ten times the normal delay as the budget approaches zero.
+    DELAY.mul_f64(1.0 + 9.0 * (1.0 - frac / PACE_THRESHOLD))
 }
 
 pub fn json_get<T: DeserializeOwned>(url: impl IntoUrl + Clone + Debug) -> Result<T> {
     err_if_closed()?;
-    thread::sleep(DELAY);
+    thread::sleep(paced_delay());
 
     let body = http_get(url)?.text()?;
     Ok(serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(
@@ -76,8 +181,54 @@ static FILENAME_QUESTION_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r#"[^?~☆:;’'",#!\(\)!\pL\pN\-_+=\[\]. ]+"#).unwrap());
 static HYPHENS: Lazy<Regex> = Lazy::new(|| Regex::new("--+").unwrap());
 
+// Maps common Latin diacritics, mostly Vietnamese, straight to their base ASCII letter. NFKD
+// decomposition in to_ascii would also reduce most of these, but đ/Đ have no decomposition and
+// are only handled here.
+fn transliterate_char(c: char) -> char {
+    match c {
+        'à' | 'á' | 'ạ' | 'ả' | 'ã' | 'â' | 'ầ' | 'ấ' | 'ậ' | 'ẩ' | 'ẫ' | 'ă' | 'ằ' | 'ắ' | 'ặ'
+        | 'ẳ' | 'ẵ' => 'a',
+        'À' | 'Á' | 'Ạ' | 'Ả' | 'Ã' | 'Â' | 'Ầ' | 'Ấ' | 'Ậ' | 'Ẩ' | 'Ẫ' | 'Ă' | 'Ằ' | 'Ắ' | 'Ặ'
+        | 'Ẳ' | 'Ẵ' => 'A',
+        'è' | 'é' | 'ẹ' | 'ẻ' | 'ẽ' | 'ê' | 'ề' | 'ế' | 'ệ' | 'ể' | 'ễ' => 'e',
+        'È' | 'É' | 'Ẹ' | 'Ẻ' | 'Ẽ' | 'Ê' | 'Ề' | 'Ế' | 'Ệ' | 'Ể' | 'Ễ' => 'E',
+        'ì' | 'í' | 'ị' | 'ỉ' | 'ĩ' => 'i',
+        'Ì' | 'Í' | 'Ị' | 'Ỉ' | 'Ĩ' => 'I',
+        'ò' | 'ó' | 'ọ' | 'ỏ' | 'õ' | 'ô' | 'ồ' | 'ố' | 'ộ' | 'ổ' | 'ỗ' | 'ơ' | 'ờ' | 'ớ' | 'ợ'
+        | 'ở' | 'ỡ' => 'o',
+        'Ò' | 'Ó' | 'Ọ' | 'Ỏ' | 'Õ' | 'Ô' | 'Ồ' | 'Ố' | 'Ộ' | 'Ổ' | 'Ỗ' | 'Ơ' | 'Ờ' | 'Ớ' | 'Ợ'
+        | 'Ở' | 'Ỡ' => 'O',
+        'ù' | 'ú' | 'ụ' | 'ủ' | 'ũ' | 'ư' | 'ừ' | 'ứ' | 'ự' | 'ử' | 'ữ' => 'u',
+        'Ù' | 'Ú' | 'Ụ' | 'Ủ' | 'Ũ' | 'Ư' | 'Ừ' | 'Ứ' | 'Ự' | 'Ử' | 'Ữ' => 'U',
+        'ỳ' | 'ý' | 'ỵ' | 'ỷ' | 'ỹ' => 'y',
+        'Ỳ' | 'Ý' | 'Ỵ' | 'Ỷ' | 'Ỹ' => 'Y',
+        'đ' => 'd',
+        'Đ' => 'D',
+        other => other,
+    }
+}
+
+// Normalizes diacritics to their base ASCII letter and drops anything left over that still isn't
+// ASCII (e.g. CJK), since those don't survive on some filesystems and sync tools.
+fn to_ascii(name: &str) -> String {
+    name.chars()
+        .map(transliterate_char)
+        .collect::<String>()
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c) && c.is_ascii())
+        .collect()
+}
+
 pub fn convert_filename(name: &str) -> String {
-    let name = if CONFIG.allow_question_marks {
+    let ascii;
+    let name = if CONFIG.read().unwrap().ascii_filenames {
+        ascii = to_ascii(name);
+        &ascii
+    } else {
+        name
+    };
+
+    let name = if CONFIG.read().unwrap().allow_question_marks {
         FILENAME_QUESTION_RE.replace_all(name, "-")
     } else {
         FILENAME_RE.replace_all(name, "-")
@@ -93,6 +244,18 @@ pub enum FindResult {
     RenameCandidate(PathBuf),
 }
 
+pub fn archive_extension() -> &'static str {
+    if CONFIG.read().unwrap().cbz { "cbz" } else { "zip" }
+}
+
+// Sends a best-effort POST and discards the result; used for the MangaDex@Home health reports,
+// where a failure to report must never abort the download itself.
+pub fn post_fire_and_forget(url: &str, body: &impl Serialize) {
+    if let Err(e) = CLIENT.post(url).json(body).send() {
+        debug!("Failed to submit report to {url}: {e}");
+    }
+}
+
 pub fn find_existing(
     expected_abs_path: &Path,
     dir: &unsync::Lazy<Result<Vec<DirEntry>>, impl FnOnce() -> Result<Vec<DirEntry>>>,
@@ -114,7 +277,7 @@ pub fn find_existing(
     let suffix = if is_dir {
         Cow::Borrowed(converted_id)
     } else {
-        Cow::Owned(format!("{converted_id}.zip"))
+        Cow::Owned(format!("{converted_id}.{}", archive_extension()))
     };
 
     let r = dir.as_ref().map_err(|e| anyhow!(e.to_string()));